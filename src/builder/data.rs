@@ -0,0 +1,52 @@
+use super::invoke::{Invoke, Identity};
+use elements;
+
+/// Data segment builder
+pub struct DataSegmentBuilder<F=Identity> {
+    callback: F,
+    index: u32,
+    offset: Vec<elements::Instruction>,
+    value: Vec<u8>,
+}
+
+impl DataSegmentBuilder {
+    /// New data segment builder
+    pub fn new() -> Self {
+        DataSegmentBuilder::with_callback(Identity)
+    }
+}
+
+impl<F> DataSegmentBuilder<F> where F: Invoke<elements::DataSegment> {
+    /// New data segment builder with bound callback
+    pub fn with_callback(callback: F) -> Self {
+        DataSegmentBuilder {
+            callback: callback,
+            index: 0,
+            offset: vec![elements::Instruction::I32Const(0), elements::Instruction::End],
+            value: Vec::new(),
+        }
+    }
+
+    /// Index of the memory this segment is initializing (always 0 under the MVP)
+    pub fn index(mut self, index: u32) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Offset expression, terminated by `Instruction::End`
+    pub fn offset(mut self, instructions: Vec<elements::Instruction>) -> Self {
+        self.offset = instructions;
+        self
+    }
+
+    /// Raw bytes to place at the offset
+    pub fn value(mut self, value: Vec<u8>) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Finish current builder, spawning resulting `DataSegment`
+    pub fn build(self) -> F::Result {
+        self.callback.invoke(elements::DataSegment::new(self.index, elements::InitExpr::new(self.offset), self.value))
+    }
+}
@@ -0,0 +1,44 @@
+use super::invoke::{Invoke, Identity};
+use elements;
+
+/// Export entry builder
+pub struct ExportBuilder<F=Identity> {
+    callback: F,
+    field: String,
+    binding: elements::Internal,
+}
+
+impl ExportBuilder {
+    /// New export builder
+    pub fn new() -> Self {
+        ExportBuilder::with_callback(Identity)
+    }
+}
+
+impl<F> ExportBuilder<F> where F: Invoke<elements::ExportEntry> {
+    /// New export entry builder with bound callback
+    pub fn with_callback(callback: F) -> Self {
+        ExportBuilder {
+            callback: callback,
+            field: String::new(),
+            binding: elements::Internal::Function(0),
+        }
+    }
+
+    /// Name of the export
+    pub fn field(mut self, field: &str) -> Self {
+        self.field = field.to_owned();
+        self
+    }
+
+    /// Internal entity this export binds to
+    pub fn internal(mut self, binding: elements::Internal) -> Self {
+        self.binding = binding;
+        self
+    }
+
+    /// Finish current builder, spawning resulting `ExportEntry`
+    pub fn build(self) -> F::Result {
+        self.callback.invoke(elements::ExportEntry::new(self.field, self.binding))
+    }
+}
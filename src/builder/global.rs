@@ -0,0 +1,54 @@
+use super::invoke::{Invoke, Identity};
+use elements;
+
+/// Global entry builder
+pub struct GlobalBuilder<F=Identity> {
+    callback: F,
+    value_type: elements::ValueType,
+    is_mutable: bool,
+    init_expr: Vec<elements::Instruction>,
+}
+
+impl GlobalBuilder {
+    /// New global builder
+    pub fn new() -> Self {
+        GlobalBuilder::with_callback(Identity)
+    }
+}
+
+impl<F> GlobalBuilder<F> where F: Invoke<elements::GlobalEntry> {
+    /// New global entry builder with bound callback
+    pub fn with_callback(callback: F) -> Self {
+        GlobalBuilder {
+            callback: callback,
+            value_type: elements::ValueType::I32,
+            is_mutable: false,
+            init_expr: vec![elements::Instruction::I32Const(0), elements::Instruction::End],
+        }
+    }
+
+    /// Type of the global
+    pub fn value_type(mut self, value_type: elements::ValueType) -> Self {
+        self.value_type = value_type;
+        self
+    }
+
+    /// Whether the global is mutable
+    pub fn mutable(mut self) -> Self {
+        self.is_mutable = true;
+        self
+    }
+
+    /// Initializer expression for the global, terminated by `Instruction::End`
+    pub fn init_expr(mut self, instructions: Vec<elements::Instruction>) -> Self {
+        self.init_expr = instructions;
+        self
+    }
+
+    /// Finish current builder, spawning resulting `GlobalEntry`
+    pub fn build(self) -> F::Result {
+        let global_type = elements::GlobalType::new(self.value_type, self.is_mutable);
+        let init_expr = elements::InitExpr::new(self.init_expr);
+        self.callback.invoke(elements::GlobalEntry::new(global_type, init_expr))
+    }
+}
@@ -0,0 +1,44 @@
+use super::invoke::{Invoke, Identity};
+use elements;
+
+/// Memory type builder
+pub struct MemoryBuilder<F=Identity> {
+    callback: F,
+    min: u32,
+    max: Option<u32>,
+}
+
+impl MemoryBuilder {
+    /// New memory builder
+    pub fn new() -> Self {
+        MemoryBuilder::with_callback(Identity)
+    }
+}
+
+impl<F> MemoryBuilder<F> where F: Invoke<elements::MemoryType> {
+    /// New memory builder with bound callback
+    pub fn with_callback(callback: F) -> Self {
+        MemoryBuilder {
+            callback: callback,
+            min: 0,
+            max: None,
+        }
+    }
+
+    /// Minimum size of the memory, in 64Kb pages
+    pub fn min(mut self, min: u32) -> Self {
+        self.min = min;
+        self
+    }
+
+    /// Maximum size of the memory, in 64Kb pages
+    pub fn max(mut self, max: u32) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    /// Finish current builder, spawning resulting `MemoryType`
+    pub fn build(self) -> F::Result {
+        self.callback.invoke(elements::MemoryType::new(self.min, self.max))
+    }
+}
@@ -1,6 +1,13 @@
+use std::collections::HashMap;
+use std::{error, fmt};
 use super::invoke::{Invoke, Identity};
 use super::code::{self, SignaturesBuilder};
 use super::import;
+use super::export;
+use super::global;
+use super::memory;
+use super::table;
+use super::data;
 use elements;
 
 /// Module builder
@@ -17,13 +24,130 @@ pub struct CodeLocation {
     pub body: u32,
 }
 
-#[derive(Default)]
+/// Error returned by `ModuleBuilder::try_build` when the assembled scaffold would
+/// serialize into a structurally invalid module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleBuildError {
+    /// Func entry at `func_index` in the 'functions' section references a type index
+    /// that doesn't exist in the 'types' section.
+    InvalidFunctionTypeRef {
+        func_index: u32,
+        type_ref: u32,
+    },
+    /// Function-typed import entry at `import_index` references a type index that
+    /// doesn't exist in the 'types' section.
+    InvalidImportTypeRef {
+        import_index: u32,
+        type_ref: u32,
+    },
+    /// The number of function bodies in the 'code' section doesn't match the number of
+    /// non-imported function entries in the 'functions' section.
+    CodeFunctionMismatch {
+        functions: u32,
+        bodies: u32,
+    },
+    /// The 'start' section references a function index outside the combined
+    /// import+function index space.
+    InvalidStartFunctionRef {
+        func_index: u32,
+    },
+    /// Export entry at `export_index` references a function index outside the combined
+    /// import+function index space.
+    InvalidExportFunctionRef {
+        export_index: u32,
+        func_index: u32,
+    },
+    /// Export entry at `export_index` references a global index outside the combined
+    /// import+global index space.
+    InvalidExportGlobalRef {
+        export_index: u32,
+        global_index: u32,
+    },
+    /// Export entry at `export_index` references a memory index outside the combined
+    /// import+memory index space.
+    InvalidExportMemoryRef {
+        export_index: u32,
+        memory_index: u32,
+    },
+    /// Export entry at `export_index` references a table index outside the combined
+    /// import+table index space.
+    InvalidExportTableRef {
+        export_index: u32,
+        table_index: u32,
+    },
+    /// Data segment at `data_index` references a memory index outside the combined
+    /// import+memory index space.
+    InvalidDataMemoryRef {
+        data_index: u32,
+        memory_index: u32,
+    },
+}
+
+impl fmt::Display for ModuleBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModuleBuildError::InvalidFunctionTypeRef { func_index, type_ref } =>
+                write!(f, "function entry {} references non-existent type {}", func_index, type_ref),
+            ModuleBuildError::InvalidImportTypeRef { import_index, type_ref } =>
+                write!(f, "import entry {} references non-existent type {}", import_index, type_ref),
+            ModuleBuildError::CodeFunctionMismatch { functions, bodies } =>
+                write!(f, "{} function entries but {} code bodies", functions, bodies),
+            ModuleBuildError::InvalidStartFunctionRef { func_index } =>
+                write!(f, "start section references non-existent function {}", func_index),
+            ModuleBuildError::InvalidExportFunctionRef { export_index, func_index } =>
+                write!(f, "export entry {} references non-existent function {}", export_index, func_index),
+            ModuleBuildError::InvalidExportGlobalRef { export_index, global_index } =>
+                write!(f, "export entry {} references non-existent global {}", export_index, global_index),
+            ModuleBuildError::InvalidExportMemoryRef { export_index, memory_index } =>
+                write!(f, "export entry {} references non-existent memory {}", export_index, memory_index),
+            ModuleBuildError::InvalidExportTableRef { export_index, table_index } =>
+                write!(f, "export entry {} references non-existent table {}", export_index, table_index),
+            ModuleBuildError::InvalidDataMemoryRef { data_index, memory_index } =>
+                write!(f, "data segment {} references non-existent memory {}", data_index, memory_index),
+        }
+    }
+}
+
+impl error::Error for ModuleBuildError {
+    fn description(&self) -> &str {
+        "module builder produced a structurally invalid module"
+    }
+}
+
 struct ModuleScaffold {
     pub functions: elements::FunctionsSection,
     pub types: elements::TypeSection,
     pub import: elements::ImportSection,
     pub code: elements::CodeSection,
+    pub export: elements::ExportSection,
+    pub global: elements::GlobalSection,
+    pub memory: elements::MemorySection,
+    pub table: elements::TableSection,
+    pub data: elements::DataSection,
     pub other: Vec<elements::Section>,
+    // Maps an already-emitted function type to its index in `types`, so that
+    // `resolve_type_ref` can return the existing index instead of pushing a duplicate.
+    type_map: HashMap<elements::FunctionType, u32>,
+    dedup_types: bool,
+}
+
+impl Default for ModuleScaffold {
+    fn default() -> Self {
+        ModuleScaffold {
+            functions: Default::default(),
+            types: Default::default(),
+            import: Default::default(),
+            code: Default::default(),
+            export: Default::default(),
+            global: Default::default(),
+            memory: Default::default(),
+            table: Default::default(),
+            data: Default::default(),
+            other: Vec::new(),
+            type_map: HashMap::new(),
+            dedup_types: true,
+        }
+    }
 }
 
 impl From<elements::Module> for ModuleScaffold {
@@ -32,6 +156,11 @@ impl From<elements::Module> for ModuleScaffold {
         let mut types: Option<elements::TypeSection> = None;
         let mut import: Option<elements::ImportSection> = None;
         let mut code: Option<elements::CodeSection> = None;
+        let mut export: Option<elements::ExportSection> = None;
+        let mut global: Option<elements::GlobalSection> = None;
+        let mut memory: Option<elements::MemorySection> = None;
+        let mut table: Option<elements::TableSection> = None;
+        let mut data: Option<elements::DataSection> = None;
 
         let mut sections = module.into_sections();
         while let Some(section) = sections.pop() {
@@ -40,16 +169,38 @@ impl From<elements::Module> for ModuleScaffold {
                 elements::Section::Function(sect) => { funcs = Some(sect); }
                 elements::Section::Import(sect) => { import = Some(sect); }
                 elements::Section::Code(sect) => { code = Some(sect); }
+                elements::Section::Export(sect) => { export = Some(sect); }
+                elements::Section::Global(sect) => { global = Some(sect); }
+                elements::Section::Memory(sect) => { memory = Some(sect); }
+                elements::Section::Table(sect) => { table = Some(sect); }
+                elements::Section::Data(sect) => { data = Some(sect); }
                 _ => {}
             }
         }
 
+        let types = types.unwrap_or_default();
+        let mut type_map = HashMap::new();
+        for (index, ty) in types.types().iter().enumerate() {
+            match *ty {
+                elements::Type::Function(ref func_type) => {
+                    type_map.entry(func_type.clone()).or_insert(index as u32);
+                }
+            }
+        }
+
         ModuleScaffold {
             functions: funcs.unwrap_or_default(),
-            types: types.unwrap_or_default(),
+            types: types,
             import: import.unwrap_or_default(),
             code: code.unwrap_or_default(),
+            export: export.unwrap_or_default(),
+            global: global.unwrap_or_default(),
+            memory: memory.unwrap_or_default(),
+            table: table.unwrap_or_default(),
+            data: data.unwrap_or_default(),
             other: sections,
+            type_map: type_map,
+            dedup_types: true,
         }
     }
 }
@@ -58,19 +209,46 @@ impl From<ModuleScaffold> for elements::Module {
     fn from(module: ModuleScaffold) -> Self {
         let mut sections = Vec::new();
 
+        // Sections are emitted in the order the Wasm spec requires them to appear on disk;
+        // `other` (Start, Element, Custom, or anything else pushed via `with_section`) slots in
+        // between Export and Code, where Start and Element belong.
         let types = module.types;
         if types.types().len() > 0 {
             sections.push(elements::Section::Type(types));
         }
-        let functions = module.functions;
-        if functions.entries().len() > 0 {
-            sections.push(elements::Section::Function(functions));
-        }        
         let import = module.import;
         if import.entries().len() > 0 {
             sections.push(elements::Section::Import(import));
-        }                
+        }
+        let functions = module.functions;
+        if functions.entries().len() > 0 {
+            sections.push(elements::Section::Function(functions));
+        }
+        let table = module.table;
+        if table.entries().len() > 0 {
+            sections.push(elements::Section::Table(table));
+        }
+        let memory = module.memory;
+        if memory.entries().len() > 0 {
+            sections.push(elements::Section::Memory(memory));
+        }
+        let global = module.global;
+        if global.entries().len() > 0 {
+            sections.push(elements::Section::Global(global));
+        }
+        let export = module.export;
+        if export.entries().len() > 0 {
+            sections.push(elements::Section::Export(export));
+        }
         sections.extend(module.other);
+        let code = module.code;
+        if code.bodies().len() > 0 {
+            sections.push(elements::Section::Code(code));
+        }
+        let data = module.data;
+        if data.entries().len() > 0 {
+            sections.push(elements::Section::Data(data));
+        }
         elements::Module::new(sections)
     }
 }
@@ -93,7 +271,9 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 
     /// Builder from raw module
     pub fn with_module(mut self, module: elements::Module) -> Self {
+        let dedup_types = self.module.dedup_types;
         self.module = module.into();
+        self.module.dedup_types = dedup_types;
         self
     }
 
@@ -140,8 +320,17 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
     fn resolve_type_ref(&mut self, signature: code::Signature) -> u32 {
         match signature {
             code::Signature::Inline(func_type) => {
-                self.module.types.types_mut().push(elements::Type::Function(func_type));
-                self.module.types.types().len() as u32 - 1
+                if self.module.dedup_types {
+                    if let Some(type_ref) = self.module.type_map.get(&func_type) {
+                        return *type_ref;
+                    }
+                }
+                self.module.types.types_mut().push(elements::Type::Function(func_type.clone()));
+                let type_ref = self.module.types.types().len() as u32 - 1;
+                if self.module.dedup_types {
+                    self.module.type_map.insert(func_type, type_ref);
+                }
+                type_ref
             }
             code::Signature::TypeReference(type_ref) => {
                 type_ref
@@ -149,6 +338,17 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
         }
     }
 
+    /// Disables deduplication of equal `Signature::Inline` function types.
+    ///
+    /// By default, `resolve_type_ref` reuses an existing entry in the type section when a
+    /// pushed inline signature is structurally equal to one already present. Some callers rely
+    /// on a stable 1:1 mapping between pushed signatures and type section indices; this opts
+    /// back into that behaviour.
+    pub fn without_type_dedup(mut self) -> Self {
+        self.module.dedup_types = false;
+        self
+    }
+
     /// Push one function signature, returning it's calling index.
     /// Can create corresponding type in type section.
     pub fn push_signature(&mut self, signature: code::Signature) -> u32 {
@@ -161,18 +361,9 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
     pub fn push_signatures(&mut self, signatures: code::SignatureBindings) -> Vec<u32> {
         let mut result = Vec::new();
 
-        // todo: maybe reuse existing types with the equal signatures
-        let raw_functions: Vec<u32> = signatures.into_iter().map(|binding|
-            match binding {
-                code::Signature::Inline(func_type) => {
-                    self.module.types.types_mut().push(elements::Type::Function(func_type));
-                    self.module.types.types().len() as u32 - 1
-                }
-                code::Signature::TypeReference(type_ref) => {
-                    type_ref
-                }
-            }
-        ).collect();
+        let raw_functions: Vec<u32> = signatures.into_iter()
+            .map(|binding| self.resolve_type_ref(binding))
+            .collect();
 
         for function in raw_functions {
             self.module.functions.entries_mut().push(elements::Func::new(function));
@@ -198,6 +389,221 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
         import::ImportBuilder::with_callback(self)
     }
 
+    /// Number of function imports currently in the import section.
+    ///
+    /// Wasm's `call` index space places all imported functions before module-defined ones, so
+    /// this is the offset that must be added to a `CodeLocation::signature` (an index into the
+    /// *functions* section) to get the real callable index. Computed from the current import
+    /// section on every call rather than cached, so it stays correct even if more imports are
+    /// added after functions have already been pushed.
+    fn imported_function_count(&self) -> u32 {
+        self.module.import.entries().iter()
+            .filter(|entry| match *entry.external() {
+                elements::External::Function(_) => true,
+                _ => false,
+            })
+            .count() as u32
+    }
+
+    /// The real `call`-index-space index of the function at `location`, accounting for any
+    /// function imports ahead of it. Use this instead of `location.signature` whenever the
+    /// index is going to be used as a `call` operand or anywhere else that shares the
+    /// import+function index space (e.g. `start`/export function indices).
+    pub fn callable_index(&self, location: &CodeLocation) -> u32 {
+        self.imported_function_count() + location.signature
+    }
+
+    /// With inserted export entry
+    pub fn with_export(mut self, entry: elements::ExportEntry) -> Self {
+        self.module.export.entries_mut().push(entry);
+        self
+    }
+
+    /// Export entry builder
+    pub fn export(self) -> export::ExportBuilder<Self> {
+        export::ExportBuilder::with_callback(self)
+    }
+
+    /// Export the function at `location` (as returned by `push_function`) under `field`
+    pub fn export_func(self, location: &CodeLocation, field: &str) -> Self {
+        let call_index = self.callable_index(location);
+        self.with_export(elements::ExportEntry::new(field.to_owned(), elements::Internal::Function(call_index)))
+    }
+
+    /// Export the global at `index` under `field`
+    pub fn export_global(self, index: u32, field: &str) -> Self {
+        self.with_export(elements::ExportEntry::new(field.to_owned(), elements::Internal::Global(index)))
+    }
+
+    /// Export the memory at `index` under `field`
+    pub fn export_memory(self, index: u32, field: &str) -> Self {
+        self.with_export(elements::ExportEntry::new(field.to_owned(), elements::Internal::Memory(index)))
+    }
+
+    /// With inserted global entry
+    pub fn with_global(mut self, entry: elements::GlobalEntry) -> Self {
+        self.module.global.entries_mut().push(entry);
+        self
+    }
+
+    /// Global entry builder
+    pub fn global(self) -> global::GlobalBuilder<Self> {
+        global::GlobalBuilder::with_callback(self)
+    }
+
+    /// With inserted memory type
+    pub fn with_memory(mut self, entry: elements::MemoryType) -> Self {
+        self.module.memory.entries_mut().push(entry);
+        self
+    }
+
+    /// Memory type builder
+    pub fn memory(self) -> memory::MemoryBuilder<Self> {
+        memory::MemoryBuilder::with_callback(self)
+    }
+
+    /// With inserted table type
+    pub fn with_table(mut self, entry: elements::TableType) -> Self {
+        self.module.table.entries_mut().push(entry);
+        self
+    }
+
+    /// Table type builder
+    pub fn table(self) -> table::TableBuilder<Self> {
+        table::TableBuilder::with_callback(self)
+    }
+
+    /// With inserted data segment
+    pub fn with_data(mut self, entry: elements::DataSegment) -> Self {
+        self.module.data.entries_mut().push(entry);
+        self
+    }
+
+    /// Data segment builder
+    pub fn data(self) -> data::DataSegmentBuilder<Self> {
+        data::DataSegmentBuilder::with_callback(self)
+    }
+
+    /// Verify that the cross-section indices of the assembled scaffold are in range: type
+    /// references of imports and functions, the function/body count, the typed export and
+    /// data sections, and any Start section in `other`. Element segments, other sections in
+    /// `other`, and indices inside function bodies are not checked.
+    fn validate(&self) -> Result<(), ModuleBuildError> {
+        let types_len = self.module.types.types().len() as u32;
+
+        let mut imported_functions = 0u32;
+        let mut imported_globals = 0u32;
+        let mut imported_memories = 0u32;
+        let mut imported_tables = 0u32;
+        for (index, entry) in self.module.import.entries().iter().enumerate() {
+            match *entry.external() {
+                elements::External::Function(type_ref) => {
+                    if type_ref >= types_len {
+                        return Err(ModuleBuildError::InvalidImportTypeRef {
+                            import_index: index as u32,
+                            type_ref: type_ref,
+                        });
+                    }
+                    imported_functions += 1;
+                }
+                elements::External::Global(_) => { imported_globals += 1; }
+                elements::External::Memory(_) => { imported_memories += 1; }
+                elements::External::Table(_) => { imported_tables += 1; }
+            }
+        }
+
+        for (index, func) in self.module.functions.entries().iter().enumerate() {
+            if func.type_ref() >= types_len {
+                return Err(ModuleBuildError::InvalidFunctionTypeRef {
+                    func_index: index as u32,
+                    type_ref: func.type_ref(),
+                });
+            }
+        }
+
+        let declared_functions = self.module.functions.entries().len() as u32;
+        let bodies = self.module.code.bodies().len() as u32;
+        if declared_functions != bodies {
+            return Err(ModuleBuildError::CodeFunctionMismatch {
+                functions: declared_functions,
+                bodies: bodies,
+            });
+        }
+
+        let total_functions = imported_functions + declared_functions;
+        let globals_len = imported_globals + self.module.global.entries().len() as u32;
+        let memories_len = imported_memories + self.module.memory.entries().len() as u32;
+        let tables_len = imported_tables + self.module.table.entries().len() as u32;
+
+        for (index, entry) in self.module.export.entries().iter().enumerate() {
+            match *entry.internal() {
+                elements::Internal::Function(func_index) => {
+                    if func_index >= total_functions {
+                        return Err(ModuleBuildError::InvalidExportFunctionRef {
+                            export_index: index as u32,
+                            func_index: func_index,
+                        });
+                    }
+                }
+                elements::Internal::Global(global_index) => {
+                    if global_index >= globals_len {
+                        return Err(ModuleBuildError::InvalidExportGlobalRef {
+                            export_index: index as u32,
+                            global_index: global_index,
+                        });
+                    }
+                }
+                elements::Internal::Memory(memory_index) => {
+                    if memory_index >= memories_len {
+                        return Err(ModuleBuildError::InvalidExportMemoryRef {
+                            export_index: index as u32,
+                            memory_index: memory_index,
+                        });
+                    }
+                }
+                elements::Internal::Table(table_index) => {
+                    if table_index >= tables_len {
+                        return Err(ModuleBuildError::InvalidExportTableRef {
+                            export_index: index as u32,
+                            table_index: table_index,
+                        });
+                    }
+                }
+            }
+        }
+
+        for (index, segment) in self.module.data.entries().iter().enumerate() {
+            if segment.index() >= memories_len {
+                return Err(ModuleBuildError::InvalidDataMemoryRef {
+                    data_index: index as u32,
+                    memory_index: segment.index(),
+                });
+            }
+        }
+
+        for section in self.module.other.iter() {
+            match *section {
+                elements::Section::Start(func_index) => {
+                    if func_index >= total_functions {
+                        return Err(ModuleBuildError::InvalidStartFunctionRef {
+                            func_index: func_index,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build module (final step), first checking the cross-section indices that
+    /// `validate` covers and returning the first one found out of range.
+    pub fn try_build(self) -> Result<F::Result, ModuleBuildError> {
+        self.validate()?;
+        Ok(self.build())
+    }
+
     /// Build module (final step)
     pub fn build(self) -> F::Result {
         self.callback.invoke(self.module.into())
@@ -225,7 +631,7 @@ impl<F> Invoke<code::SignatureBindings> for ModuleBuilder<F>
 }
 
 impl<F> Invoke<elements::ImportEntry> for ModuleBuilder<F>
-    where F: Invoke<elements::Module> 
+    where F: Invoke<elements::Module>
 {
     type Result = Self;
 
@@ -234,6 +640,56 @@ impl<F> Invoke<elements::ImportEntry> for ModuleBuilder<F>
     }
 }
 
+impl<F> Invoke<elements::ExportEntry> for ModuleBuilder<F>
+    where F: Invoke<elements::Module>
+{
+    type Result = Self;
+
+    fn invoke(self, entry: elements::ExportEntry) -> Self::Result {
+        self.with_export(entry)
+    }
+}
+
+impl<F> Invoke<elements::GlobalEntry> for ModuleBuilder<F>
+    where F: Invoke<elements::Module>
+{
+    type Result = Self;
+
+    fn invoke(self, entry: elements::GlobalEntry) -> Self::Result {
+        self.with_global(entry)
+    }
+}
+
+impl<F> Invoke<elements::MemoryType> for ModuleBuilder<F>
+    where F: Invoke<elements::Module>
+{
+    type Result = Self;
+
+    fn invoke(self, entry: elements::MemoryType) -> Self::Result {
+        self.with_memory(entry)
+    }
+}
+
+impl<F> Invoke<elements::TableType> for ModuleBuilder<F>
+    where F: Invoke<elements::Module>
+{
+    type Result = Self;
+
+    fn invoke(self, entry: elements::TableType) -> Self::Result {
+        self.with_table(entry)
+    }
+}
+
+impl<F> Invoke<elements::DataSegment> for ModuleBuilder<F>
+    where F: Invoke<elements::Module>
+{
+    type Result = Self;
+
+    fn invoke(self, entry: elements::DataSegment) -> Self::Result {
+        self.with_data(entry)
+    }
+}
+
 /// Start new module builder
 pub fn module() -> ModuleBuilder {
     ModuleBuilder::new()
@@ -267,4 +723,204 @@ mod tests {
         assert_eq!(module.functions_section().expect("function section to exist").entries().len(), 1);
     }
 
+    #[test]
+    fn dedup_types() {
+        let module = module()
+            .functions()
+                .signature().with_param(::elements::ValueType::I32).build()
+                .bind()
+                .signature().with_param(::elements::ValueType::I32).build()
+                .bind()
+            .build();
+
+        assert_eq!(module.type_section().expect("type section to exist").types().len(), 1);
+        assert_eq!(module.functions_section().expect("function section to exist").entries().len(), 2);
+    }
+
+    #[test]
+    fn without_type_dedup() {
+        let module = module()
+            .without_type_dedup()
+            .functions()
+                .signature().with_param(::elements::ValueType::I32).build()
+                .bind()
+                .signature().with_param(::elements::ValueType::I32).build()
+                .bind()
+            .build();
+
+        assert_eq!(module.type_section().expect("type section to exist").types().len(), 2);
+        assert_eq!(module.functions_section().expect("function section to exist").entries().len(), 2);
+    }
+
+    #[test]
+    fn without_type_dedup_survives_with_module() {
+        let existing = module().build();
+        let module = module()
+            .without_type_dedup()
+            .with_module(existing)
+            .functions()
+                .signature().with_param(::elements::ValueType::I32).build()
+                .bind()
+                .signature().with_param(::elements::ValueType::I32).build()
+                .bind()
+            .build();
+
+        assert_eq!(module.type_section().expect("type section to exist").types().len(), 2);
+        assert_eq!(module.functions_section().expect("function section to exist").entries().len(), 2);
+    }
+
+    #[test]
+    fn try_build_ok_on_empty_module() {
+        assert!(module().try_build().is_ok());
+    }
+
+    #[test]
+    fn try_build_detects_code_function_mismatch() {
+        use super::super::code::Signature;
+
+        let mut builder = module();
+        builder.push_signature(Signature::Inline(::elements::FunctionType::new(Vec::new(), None)));
+
+        assert_eq!(
+            builder.try_build().err(),
+            Some(super::ModuleBuildError::CodeFunctionMismatch { functions: 1, bodies: 0 })
+        );
+    }
+
+    #[test]
+    fn try_build_detects_invalid_type_ref() {
+        use super::super::code::Signature;
+
+        let mut builder = module();
+        builder.push_signature(Signature::TypeReference(7));
+
+        assert_eq!(
+            builder.try_build().err(),
+            Some(super::ModuleBuildError::InvalidFunctionTypeRef { func_index: 0, type_ref: 7 })
+        );
+    }
+
+    #[test]
+    fn try_build_detects_invalid_export_global_ref() {
+        let builder = module()
+            .export().field("g").internal(::elements::Internal::Global(0)).build();
+
+        assert_eq!(
+            builder.try_build().err(),
+            Some(super::ModuleBuildError::InvalidExportGlobalRef { export_index: 0, global_index: 0 })
+        );
+    }
+
+    #[test]
+    fn try_build_detects_invalid_export_memory_ref() {
+        let builder = module()
+            .export().field("m").internal(::elements::Internal::Memory(0)).build();
+
+        assert_eq!(
+            builder.try_build().err(),
+            Some(super::ModuleBuildError::InvalidExportMemoryRef { export_index: 0, memory_index: 0 })
+        );
+    }
+
+    #[test]
+    fn try_build_detects_invalid_export_table_ref() {
+        let builder = module()
+            .export().field("t").internal(::elements::Internal::Table(0)).build();
+
+        assert_eq!(
+            builder.try_build().err(),
+            Some(super::ModuleBuildError::InvalidExportTableRef { export_index: 0, table_index: 0 })
+        );
+    }
+
+    #[test]
+    fn try_build_detects_invalid_data_memory_ref() {
+        let builder = module()
+            .data().index(1)
+                .offset(vec![::elements::Instruction::I32Const(0), ::elements::Instruction::End])
+                .value(vec![1, 2, 3])
+                .build();
+
+        assert_eq!(
+            builder.try_build().err(),
+            Some(super::ModuleBuildError::InvalidDataMemoryRef { data_index: 0, memory_index: 1 })
+        );
+    }
+
+    #[test]
+    fn export_global_memory_table_data() {
+        let module = module()
+            .table().min(1).max(2).build()
+            .memory().min(1).max(1).build()
+            .global().value_type(::elements::ValueType::I32).mutable().build()
+            .data().offset(vec![::elements::Instruction::I32Const(0), ::elements::Instruction::End])
+                .value(vec![1, 2, 3, 4]).build()
+            .export().field("memory").internal(::elements::Internal::Memory(0)).build()
+            .build();
+
+        assert_eq!(module.table_section().expect("table section to exist").entries().len(), 1);
+        assert_eq!(module.memory_section().expect("memory section to exist").entries().len(), 1);
+        assert_eq!(module.global_section().expect("global section to exist").entries().len(), 1);
+        assert_eq!(module.data_section().expect("data section to exist").entries().len(), 1);
+        assert_eq!(module.export_section().expect("export section to exist").entries().len(), 1);
+    }
+
+    #[test]
+    fn callable_index_accounts_for_imports() {
+        let builder = module()
+            .with_import(::elements::ImportEntry::new(
+                "env".to_owned(),
+                "f".to_owned(),
+                ::elements::External::Function(0),
+            ));
+
+        let location = super::CodeLocation { signature: 0, body: 0 };
+        assert_eq!(builder.callable_index(&location), 1);
+    }
+
+    #[test]
+    fn code_section_emitted_after_push_function() {
+        use super::code::{FunctionDefinition, Signature};
+
+        let mut builder = module();
+        builder.push_function(FunctionDefinition {
+            signature: Signature::Inline(::elements::FunctionType::new(Vec::new(), None)),
+            code: ::elements::FuncBody::new(Vec::new(), ::elements::Instructions::new(vec![::elements::Instruction::End])),
+        });
+
+        let module = builder.build();
+        assert_eq!(module.functions_section().expect("function section to exist").entries().len(), 1);
+        assert_eq!(module.code_section().expect("code section to exist").bodies().len(), 1);
+    }
+
+    #[test]
+    fn section_order_matches_spec() {
+        let module = module()
+            .with_import(::elements::ImportEntry::new("env".to_owned(), "f".to_owned(), ::elements::External::Function(0)))
+            .table().min(1).build()
+            .memory().min(1).build()
+            .global().value_type(::elements::ValueType::I32).build()
+            .export().field("memory").internal(::elements::Internal::Memory(0)).build()
+            .data().offset(vec![::elements::Instruction::I32Const(0), ::elements::Instruction::End]).value(vec![1]).build()
+            .build();
+
+        let kinds: Vec<&str> = module.sections().iter().map(|section| match *section {
+            ::elements::Section::Type(_) => "type",
+            ::elements::Section::Import(_) => "import",
+            ::elements::Section::Function(_) => "function",
+            ::elements::Section::Table(_) => "table",
+            ::elements::Section::Memory(_) => "memory",
+            ::elements::Section::Global(_) => "global",
+            ::elements::Section::Export(_) => "export",
+            ::elements::Section::Start(_) => "start",
+            ::elements::Section::Code(_) => "code",
+            ::elements::Section::Data(_) => "data",
+            _ => "other",
+        }).collect();
+
+        // Per the Wasm spec's on-disk section order; `other` (Start/Element/Custom) would slot
+        // in between Export and Code, but none is present here.
+        assert_eq!(kinds, vec!["import", "table", "memory", "global", "export", "data"]);
+    }
+
 }
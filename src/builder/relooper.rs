@@ -0,0 +1,452 @@
+use std::collections::{HashMap, HashSet};
+use elements::{Instruction, BlockType};
+
+/// How control leaves a `BasicBlock` once its straight-line instructions have run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// Unconditionally continues into the given block. Usually the next one in program order,
+    /// but not guaranteed — e.g. a join reached from more than one predecessor can't follow
+    /// all of them — so when it isn't, an unconditional branch to it is emitted instead.
+    Fallthrough(usize),
+    /// Unconditionally jumps to the given block.
+    Branch(usize),
+    /// Pops an `i32` condition off the stack and jumps to `then` if it is non-zero; otherwise
+    /// continues into `otherwise`. `otherwise` need not immediately follow this block (e.g. an
+    /// "if" with no "else" typically has `otherwise` as a merge point also reached through
+    /// `then`) — when it doesn't, an unconditional branch to it is emitted right after the
+    /// conditional one, exactly as for a plain `Branch` target.
+    Conditional { then: usize, otherwise: usize },
+    /// Returns from the function.
+    Return,
+}
+
+/// A single basic block: a straight-line sequence of instructions plus how control leaves it.
+pub struct BasicBlock {
+    instructions: Vec<Instruction>,
+    edge: Edge,
+}
+
+impl BasicBlock {
+    /// New basic block with the given body and outgoing edge.
+    pub fn new(instructions: Vec<Instruction>, edge: Edge) -> Self {
+        BasicBlock { instructions: instructions, edge: edge }
+    }
+
+    fn targets(&self) -> Vec<usize> {
+        match self.edge {
+            Edge::Fallthrough(target) | Edge::Branch(target) => vec![target],
+            Edge::Conditional { then, otherwise } => vec![then, otherwise],
+            Edge::Return => Vec::new(),
+        }
+    }
+}
+
+// A block scope ends just before the target block; a loop scope starts at its header and
+// branching to it jumps back to the top, not past the end.
+enum ScopeTag {
+    Block { end: usize },
+    Loop { header_pos: usize, end: usize },
+}
+
+impl ScopeTag {
+    fn end(&self) -> usize {
+        match *self {
+            ScopeTag::Block { end } => end,
+            ScopeTag::Loop { end, .. } => end,
+        }
+    }
+}
+
+/// Lowers a control-flow graph of `BasicBlock`s into structured Wasm control flow
+/// (`block`/`loop`/`br`/`br_if`), producing a flat instruction list suitable for
+/// `code::FunctionDefinition`.
+///
+/// Implements a reverse-postorder "stackifier" pass: a DFS from the entry block assigns every
+/// reachable block a position in the emitted order and flags back edges (edges to an
+/// already-on-stack ancestor), whose targets become loop headers. Each loop header and its
+/// body are wrapped in a `loop` scope with an enclosing `block` as its break target. Forward
+/// branches that skip over intervening blocks open a `block` scope starting at the earliest
+/// block that branches to it and ending just before the target; scopes that would otherwise
+/// overlap without nesting are widened so the later-ending one encloses the earlier-ending
+/// one, keeping every scope properly nested. Unreachable blocks are dropped. The returned
+/// list does not include the closing `End` of the function body.
+pub struct Relooper {
+    blocks: Vec<BasicBlock>,
+}
+
+impl Relooper {
+    /// New relooper over the given basic blocks, indexed as they appear in the vector.
+    pub fn new(blocks: Vec<BasicBlock>) -> Self {
+        Relooper { blocks: blocks }
+    }
+
+    /// Lower the graph reachable from `entry` into a flat, structured instruction list.
+    pub fn render(&self, entry: usize) -> Vec<Instruction> {
+        let (order, back_edges) = self.reverse_postorder(entry);
+        let position: HashMap<usize, usize> = order.iter().enumerate()
+            .map(|(pos, &block)| (block, pos)).collect();
+
+        // A loop spans from its header's position up to (and including) the last block that
+        // branches back to it.
+        let mut loop_end: HashMap<usize, usize> = HashMap::new();
+        for &(source, target) in back_edges.iter() {
+            let header_pos = position[&target];
+            let end = position[&source] + 1;
+            let slot = loop_end.entry(header_pos).or_insert(end);
+            if end > *slot { *slot = end; }
+        }
+
+        // Forward-branch block scopes: end position -> earliest position that must already
+        // have the scope open. A `Conditional`'s `then` always needs one, since a `BrIf` has to
+        // land somewhere regardless of adjacency; `Branch`, `Fallthrough`, and a `Conditional`'s
+        // `otherwise` only need one when their target isn't literally the very next block (a
+        // join reached from more than one predecessor can't follow all of them, so this is not
+        // guaranteed just because the edge is a `Fallthrough`).
+        let mut required: HashMap<usize, usize> = HashMap::new();
+        for (pos, &block) in order.iter().enumerate() {
+            let targets: Vec<(usize, bool)> = match self.blocks[block].edge {
+                Edge::Branch(target) | Edge::Fallthrough(target) => vec![(target, false)],
+                Edge::Conditional { then, otherwise } => vec![(then, true), (otherwise, false)],
+                Edge::Return => Vec::new(),
+            };
+            for (target, is_conditional) in targets {
+                let target_pos = match position.get(&target) {
+                    Some(&p) => p,
+                    None => continue, // unreachable block, dropped
+                };
+                if back_edges.contains(&(block, target)) {
+                    continue; // handled by the loop's own scope
+                }
+                if target_pos > pos && (is_conditional || target_pos != pos + 1) {
+                    let slot = required.entry(target_pos).or_insert(pos);
+                    if pos < *slot { *slot = pos; }
+                }
+            }
+        }
+        // The enclosing break-block of a loop is just another scope that happens to share the
+        // loop's end position; merge it into the same requirement so a crossing scope widens
+        // both together.
+        for (&header_pos, &end) in loop_end.iter() {
+            let slot = required.entry(end).or_insert(header_pos);
+            if header_pos < *slot { *slot = header_pos; }
+        }
+
+        let starts = Self::resolve_nesting(&required);
+
+        let mut opens: Vec<Vec<ScopeTag>> = (0..order.len() + 1).map(|_| Vec::new()).collect();
+        let mut closes_at: Vec<usize> = vec![0; order.len() + 1];
+
+        for (&end, &start) in starts.iter() {
+            opens[start].push(ScopeTag::Block { end: end });
+            closes_at[end] += 1;
+        }
+        for (&header_pos, &end) in loop_end.iter() {
+            opens[header_pos].push(ScopeTag::Loop { header_pos: header_pos, end: end });
+            closes_at[end] += 1;
+        }
+        for tags in opens.iter_mut() {
+            // Sorted so the smallest (innermost) end comes first and the largest (outermost)
+            // comes last; opens are then taken off the back, so the outermost (and, for a
+            // loop's header, the enclosing block before the loop itself) is emitted first.
+            tags.sort_by_key(|tag| {
+                let loop_sorts_first = match *tag { ScopeTag::Loop { .. } => 0, ScopeTag::Block { .. } => 1 };
+                (tag.end(), loop_sorts_first)
+            });
+        }
+
+        let mut out = Vec::new();
+        let mut scope_stack: Vec<ScopeTag> = Vec::new();
+
+        for (pos, &block) in order.iter().enumerate() {
+            for _ in 0..closes_at[pos] {
+                out.push(Instruction::End);
+                scope_stack.pop();
+            }
+            while let Some(tag) = opens[pos].pop() {
+                out.push(match tag {
+                    ScopeTag::Block { .. } => Instruction::Block(BlockType::NoResult),
+                    ScopeTag::Loop { .. } => Instruction::Loop(BlockType::NoResult),
+                });
+                scope_stack.push(tag);
+            }
+
+            out.extend(self.blocks[block].instructions.iter().cloned());
+
+            match self.blocks[block].edge {
+                Edge::Return => out.push(Instruction::Return),
+                Edge::Branch(target) | Edge::Fallthrough(target) => {
+                    let target_pos = position[&target];
+                    let is_back = back_edges.contains(&(block, target));
+                    if !(target_pos == pos + 1 && !is_back) {
+                        let depth = Self::depth_of(&scope_stack, is_back, target_pos);
+                        out.push(Instruction::Br(depth));
+                    }
+                }
+                Edge::Conditional { then, otherwise } => {
+                    let then_is_back = back_edges.contains(&(block, then));
+                    let then_depth = Self::depth_of(&scope_stack, then_is_back, position[&then]);
+                    out.push(Instruction::BrIf(then_depth));
+
+                    let otherwise_pos = position[&otherwise];
+                    let otherwise_is_back = back_edges.contains(&(block, otherwise));
+                    if !(otherwise_pos == pos + 1 && !otherwise_is_back) {
+                        let otherwise_depth = Self::depth_of(&scope_stack, otherwise_is_back, otherwise_pos);
+                        out.push(Instruction::Br(otherwise_depth));
+                    }
+                }
+            }
+        }
+        for _ in 0..closes_at[order.len()] {
+            out.push(Instruction::End);
+        }
+
+        out
+    }
+
+    // Depth-first search from `entry`, returning the reverse-postorder list of reachable
+    // blocks and the set of back edges (as (source, target) pairs) discovered along the way.
+    fn reverse_postorder(&self, entry: usize) -> (Vec<usize>, HashSet<(usize, usize)>) {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut on_stack = vec![false; self.blocks.len()];
+        let mut postorder = Vec::new();
+        let mut back_edges = HashSet::new();
+
+        let mut stack: Vec<(usize, usize)> = vec![(entry, 0)];
+        visited[entry] = true;
+        on_stack[entry] = true;
+
+        'outer: while let Some(&mut (node, ref mut next)) = stack.last_mut() {
+            let successors = self.blocks[node].targets();
+            while *next < successors.len() {
+                let target = successors[*next];
+                *next += 1;
+                if on_stack[target] {
+                    back_edges.insert((node, target));
+                    continue;
+                }
+                if !visited[target] {
+                    visited[target] = true;
+                    on_stack[target] = true;
+                    stack.push((target, 0));
+                    continue 'outer;
+                }
+            }
+            on_stack[node] = false;
+            postorder.push(node);
+            stack.pop();
+        }
+
+        postorder.reverse();
+        (postorder, back_edges)
+    }
+
+    // Widens each required scope's start so that scopes which would otherwise cross (overlap
+    // without one nesting inside the other) become properly nested, pulling the earlier-ending
+    // scope inside the later-ending one.
+    fn resolve_nesting(required: &HashMap<usize, usize>) -> HashMap<usize, usize> {
+        let mut ends: Vec<usize> = required.keys().cloned().collect();
+        ends.sort();
+
+        let mut starts: HashMap<usize, usize> = HashMap::new();
+        for &end in ends.iter() {
+            let mut start = required[&end];
+            loop {
+                let mut widened = false;
+                for &other_end in ends.iter() {
+                    if other_end >= end { continue; }
+                    let other_start = starts[&other_end];
+                    if start < other_end && other_start < start {
+                        start = other_start;
+                        widened = true;
+                    }
+                }
+                if !widened { break; }
+            }
+            starts.insert(end, start);
+        }
+
+        starts
+    }
+
+    // Relative branch depth to the scope (block ending at `target_pos`, or loop headed at
+    // `target_pos`) nearest the top of the scope stack.
+    fn depth_of(scope_stack: &[ScopeTag], is_back_edge: bool, target_pos: usize) -> u32 {
+        for (index, tag) in scope_stack.iter().enumerate().rev() {
+            let hit = match *tag {
+                ScopeTag::Loop { header_pos, .. } => is_back_edge && header_pos == target_pos,
+                ScopeTag::Block { end } => !is_back_edge && end == target_pos,
+            };
+            if hit {
+                return (scope_stack.len() - 1 - index) as u32;
+            }
+        }
+        panic!("relooper: no enclosing scope found for branch target");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Relooper, BasicBlock, Edge};
+    use elements::{Instruction, BlockType};
+
+    #[test]
+    fn if_else() {
+        // 0: br_if 2 -> (2: then)
+        // 1: (else) -> branches to 3
+        // 2: (then) -> falls through to 3
+        // 3: join, return
+        let blocks = vec![
+            BasicBlock::new(vec![Instruction::I32Const(1)], Edge::Conditional { then: 2, otherwise: 1 }),
+            BasicBlock::new(vec![Instruction::I32Const(10)], Edge::Branch(3)),
+            BasicBlock::new(vec![Instruction::I32Const(20)], Edge::Fallthrough(3)),
+            BasicBlock::new(vec![], Edge::Return),
+        ];
+
+        let instructions = Relooper::new(blocks).render(0);
+
+        // The branch out of block 1 has to clear both the scope that lands on block 2's
+        // content and the one that lands on block 3's, so the two necessarily nest.
+        assert_eq!(instructions, vec![
+            Instruction::Block(BlockType::NoResult),
+                Instruction::Block(BlockType::NoResult),
+                    Instruction::I32Const(1),
+                    Instruction::BrIf(0),
+                    Instruction::I32Const(10),
+                    Instruction::Br(1),
+                Instruction::End,
+                Instruction::I32Const(20),
+            Instruction::End,
+            Instruction::Return,
+        ]);
+    }
+
+    #[test]
+    fn if_without_else() {
+        // 0: br_if 1 -> (1: then); otherwise falls through to merge block 2
+        // 1: (then) -> falls through to 2
+        // 2: merge, return
+        //
+        // `otherwise` (block 2) is also reached through `then`'s fallthrough, so it can't be
+        // placed immediately after block 0 in the final order; it needs its own branch.
+        let blocks = vec![
+            BasicBlock::new(vec![Instruction::I32Const(1)], Edge::Conditional { then: 1, otherwise: 2 }),
+            BasicBlock::new(vec![Instruction::I32Const(10)], Edge::Fallthrough(2)),
+            BasicBlock::new(vec![], Edge::Return),
+        ];
+
+        let instructions = Relooper::new(blocks).render(0);
+
+        assert_eq!(instructions, vec![
+            Instruction::Block(BlockType::NoResult),
+                Instruction::Block(BlockType::NoResult),
+                    Instruction::I32Const(1),
+                    Instruction::BrIf(0),
+                    Instruction::Br(1),
+                Instruction::End,
+                Instruction::I32Const(10),
+            Instruction::End,
+            Instruction::Return,
+        ]);
+    }
+
+    #[test]
+    fn fallthrough_join_from_both_arms() {
+        // 0: br_if 1 -> (1: then); otherwise falls through to 2
+        // 1: (then) -> falls through to 3 (join)
+        // 2: (else) -> falls through to 3 (join)
+        // 3: join, return
+        //
+        // Neither arm is a `Branch`: both reach the join purely via `Fallthrough`. Since the
+        // join is reached from two predecessors, it can follow at most one of them in the final
+        // order — the other's `Fallthrough` needs a real branch, exactly like `if_without_else`'s
+        // `Conditional::otherwise` does.
+        let blocks = vec![
+            BasicBlock::new(vec![Instruction::I32Const(0)], Edge::Conditional { then: 1, otherwise: 2 }),
+            BasicBlock::new(vec![Instruction::I32Const(100)], Edge::Fallthrough(3)),
+            BasicBlock::new(vec![Instruction::I32Const(200)], Edge::Fallthrough(3)),
+            BasicBlock::new(vec![Instruction::I32Const(300)], Edge::Return),
+        ];
+
+        let instructions = Relooper::new(blocks).render(0);
+
+        // Block 2 lands right after block 0, so block 0's `otherwise` edge is free; block 1 is
+        // reached only by the `BrIf` skipping over block 2 and lands right before the join, so
+        // block 1's `Fallthrough` is free while block 2's needs a `Br` to the join. The scope
+        // landing on the join also has to widen to enclose the one landing on block 1, just as
+        // in `if_else`.
+        assert_eq!(instructions, vec![
+            Instruction::Block(BlockType::NoResult),
+                Instruction::Block(BlockType::NoResult),
+                    Instruction::I32Const(0),
+                    Instruction::BrIf(0),
+                    Instruction::I32Const(200),
+                    Instruction::Br(1),
+                Instruction::End,
+                Instruction::I32Const(100),
+            Instruction::End,
+            Instruction::I32Const(300),
+            Instruction::Return,
+        ]);
+    }
+
+    #[test]
+    fn nested_loop() {
+        // 0: falls through to 1
+        // 1: outer loop header, br_if enters inner loop (2), else falls through to 4 (exit)
+        // 2: inner loop header, br_if back to itself (2), else falls through to 3
+        // 3: branches back to 1 (closes the outer loop)
+        // 4: return
+        let blocks = vec![
+            BasicBlock::new(vec![], Edge::Fallthrough(1)),
+            BasicBlock::new(vec![Instruction::I32Const(1)], Edge::Conditional { then: 2, otherwise: 4 }),
+            BasicBlock::new(vec![Instruction::I32Const(2)], Edge::Conditional { then: 2, otherwise: 3 }),
+            BasicBlock::new(vec![Instruction::I32Const(3)], Edge::Branch(1)),
+            BasicBlock::new(vec![Instruction::I32Const(4)], Edge::Return),
+        ];
+
+        let instructions = Relooper::new(blocks).render(0);
+
+        assert_eq!(instructions, vec![
+            Instruction::Block(BlockType::NoResult),
+                Instruction::Loop(BlockType::NoResult),
+                    Instruction::Block(BlockType::NoResult),
+                        Instruction::I32Const(1),
+                        Instruction::BrIf(0),
+                        Instruction::I32Const(4),
+                        Instruction::Return,
+                    Instruction::End,
+                    Instruction::Block(BlockType::NoResult),
+                        Instruction::Loop(BlockType::NoResult),
+                            Instruction::I32Const(2),
+                            Instruction::BrIf(0),
+                        Instruction::End,
+                    Instruction::End,
+                    Instruction::I32Const(3),
+                    Instruction::Br(0),
+                Instruction::End,
+            Instruction::End,
+        ]);
+    }
+
+    #[test]
+    fn simple_loop() {
+        // 0: loop header, br_if back to 0, else fall through to 1
+        // 1: return
+        let blocks = vec![
+            BasicBlock::new(vec![Instruction::I32Const(1)], Edge::Conditional { then: 0, otherwise: 1 }),
+            BasicBlock::new(vec![], Edge::Return),
+        ];
+
+        let instructions = Relooper::new(blocks).render(0);
+
+        assert_eq!(instructions, vec![
+            Instruction::Block(BlockType::NoResult),
+                Instruction::Loop(BlockType::NoResult),
+                    Instruction::I32Const(1),
+                    Instruction::BrIf(0),
+                Instruction::End,
+            Instruction::End,
+            Instruction::Return,
+        ]);
+    }
+}
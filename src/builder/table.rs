@@ -0,0 +1,44 @@
+use super::invoke::{Invoke, Identity};
+use elements;
+
+/// Table type builder
+pub struct TableBuilder<F=Identity> {
+    callback: F,
+    min: u32,
+    max: Option<u32>,
+}
+
+impl TableBuilder {
+    /// New table builder
+    pub fn new() -> Self {
+        TableBuilder::with_callback(Identity)
+    }
+}
+
+impl<F> TableBuilder<F> where F: Invoke<elements::TableType> {
+    /// New table builder with bound callback
+    pub fn with_callback(callback: F) -> Self {
+        TableBuilder {
+            callback: callback,
+            min: 0,
+            max: None,
+        }
+    }
+
+    /// Minimum number of elements in the table
+    pub fn min(mut self, min: u32) -> Self {
+        self.min = min;
+        self
+    }
+
+    /// Maximum number of elements in the table
+    pub fn max(mut self, max: u32) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    /// Finish current builder, spawning resulting `TableType`
+    pub fn build(self) -> F::Result {
+        self.callback.invoke(elements::TableType::new(self.min, self.max))
+    }
+}